@@ -1,7 +1,35 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{env, path::PathBuf};
 
+use crate::structs::{Method, Status};
+
 pub struct Config {
     pub directory: Box<PathBuf>,
+    log_writer: Mutex<LogWriter>,
+}
+
+enum LogWriter {
+    Stdout(Stdout),
+    File(BufWriter<std::fs::File>),
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LogWriter::Stdout(w) => w.write(buf),
+            LogWriter::File(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogWriter::Stdout(w) => w.flush(),
+            LogWriter::File(w) => w.flush(),
+        }
+    }
 }
 
 impl Config {
@@ -10,15 +38,102 @@ impl Config {
             "{}/public",
             env::current_dir().unwrap().to_str().unwrap()
         ));
+        let mut log_file = None;
 
         while let Some(arg) = args.next() {
-            if arg.as_str() == "--directory" {
-                directory = PathBuf::from(args.next().expect("invalid args"));
+            match arg.as_str() {
+                "--directory" => {
+                    directory = PathBuf::from(args.next().expect("invalid args"));
+                }
+                "--log-file" => {
+                    log_file = Some(PathBuf::from(args.next().expect("invalid args")));
+                }
+                _ => {}
             }
         }
 
+        let log_writer = match log_file {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .expect("failed to open log file");
+                LogWriter::File(BufWriter::new(file))
+            }
+            None => LogWriter::Stdout(io::stdout()),
+        };
+
         Self {
             directory: Box::new(directory),
+            log_writer: Mutex::new(log_writer),
         }
     }
+
+    /// Appends one access-log line for a handled request: remote address,
+    /// timestamp, method, path, HTTP version, status code and response body
+    /// size. Serialized through a mutex since connections are handled on
+    /// separate `tokio::task::spawn_blocking` threads sharing this `Config`.
+    pub fn log_access(
+        &self,
+        remote_addr: &str,
+        method: &Method,
+        path: &str,
+        version: &str,
+        status: &Status,
+        body_len: usize,
+    ) {
+        let line = format!(
+            "{} - - [{}] \"{} {} {}\" {} {}\n",
+            remote_addr,
+            now_timestamp(),
+            method,
+            path,
+            version,
+            status.code(),
+            body_len,
+        );
+
+        let mut writer = self.log_writer.lock().unwrap();
+        if let Err(e) = writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush())
+        {
+            eprintln!("error writing access log: {}", e);
+        }
+    }
+}
+
+fn now_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let days = secs / 86_400;
+    let time_of_day = secs % 86_400;
+    let (hour, minute, second) = (time_of_day / 3600, (time_of_day / 60) % 60, time_of_day % 60);
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Converts a day count since the Unix epoch into a (year, month, day) civil
+/// date, using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = (z - era * 146_097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = if m <= 2 { y + 1 } else { y };
+
+    (year, m, d)
 }
@@ -1,8 +1,11 @@
 use anyhow::Result;
+use flate2::read::GzDecoder;
 use std::fs;
 use std::io::Read;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use std::{
     io::{BufRead, BufReader},
     net::TcpStream,
@@ -15,88 +18,177 @@ use structs::*;
 pub mod config;
 use config::Config;
 
-pub async fn handle_connection(mut stream: TcpStream, config: Arc<Config>) -> Result<()> {
-    let mut reader = BufReader::new(&mut stream);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
 
-    let mut first_line = String::new();
-    reader.read_line(&mut first_line)?;
+/// Synchronous by design: every read/write here is blocking `std::net`
+/// I/O, so callers must run this on a blocking-safe thread (e.g. via
+/// `tokio::task::spawn_blocking`) rather than a regular async task, or a
+/// handful of idle keep-alive connections can starve the async worker pool.
+pub fn handle_connection(mut stream: TcpStream, config: Arc<Config>) -> Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let remote_addr = stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|_| "-".to_string());
 
-    let parts = first_line.split_whitespace().collect::<Vec<&str>>();
-    let method = Method::from_str(parts[0])?;
-    let path = parts[1].to_string();
-    let version = parts[2].to_string();
+    let mut reader = BufReader::new(&mut stream);
 
-    let mut header_lines = Vec::new();
     loop {
-        let mut line = String::new();
-        reader.read_line(&mut line)?;
-        if line == "\r\n" {
+        let mut first_line = String::new();
+        if reader.read_line(&mut first_line)? == 0 {
             break;
         }
-        header_lines.push(line.trim_end().to_string());
-    }
-    let headers = Headers::from(header_lines)?;
-
-    let mut body_lines = Vec::new();
-    if let Some(length) = headers.get_content_length() {
-        if length != 0 {
-            let mut lines = vec![0; length];
-            reader.read_exact(&mut lines)?;
-            body_lines = String::from_utf8_lossy(&lines[..])
-                .lines()
-                .map(|line| line.to_string())
-                .collect();
+
+        let parts = first_line.split_whitespace().collect::<Vec<&str>>();
+        let method = Method::from_str(parts[0])?;
+        let path = parts[1].to_string();
+        let version = parts[2].to_string();
+
+        let mut header_lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            if line == "\r\n" {
+                break;
+            }
+            header_lines.push(line.trim_end().to_string());
         }
-    }
+        let headers = Headers::from(header_lines)?;
 
-    let body = Body::from_lines(body_lines);
+        let mut body_lines = Vec::new();
+        if let Some(length) = headers.get_content_length() {
+            if length != 0 {
+                let mut lines = vec![0; length];
+                reader.read_exact(&mut lines)?;
+                body_lines = String::from_utf8_lossy(&lines[..])
+                    .lines()
+                    .map(|line| line.to_string())
+                    .collect();
+            }
+        }
 
-    let request = Request::new(method, path, version, headers, body);
+        let body = Body::from_lines(body_lines);
 
-    let mut response = Response::new();
+        let request = Request::new(method, path, version, headers, body);
+        let keep_alive = request.wants_keep_alive();
 
-    match request.path.as_str() {
-        "/" => {
-            response.set_status(Status::Ok);
-        }
-        x if x.starts_with("/echo/") => {
-            let text = request.path.split_at(6).1;
-            response.set_plain_text_body(Body::from_str(text));
+        let mut response = Response::new();
 
-            response.apply_compression(&request);
-        }
-        "/user-agent" => {
-            let default_user_agent = String::from("None");
-            let user_agent = request
-                .headers
-                .get_user_agent()
-                .unwrap_or(&default_user_agent);
-
-            response.set_plain_text_body(Body::from_str(user_agent));
-        }
-        x if x.starts_with("/files/") => {
-            handle_files(&request, &mut response, &config);
+        match request.path.as_str() {
+            "/" => {
+                response.set_status(Status::Ok);
+            }
+            x if x.starts_with("/echo/") => {
+                let text = request.path.split_at(6).1;
+                response.set_plain_text_body(Body::from_str(text));
+
+                response.apply_compression(&request);
+            }
+            "/user-agent" => {
+                let default_user_agent = String::from("None");
+                let user_agent = request
+                    .headers
+                    .get_user_agent()
+                    .unwrap_or(&default_user_agent);
+
+                response.set_plain_text_body(Body::from_str(user_agent));
+            }
+            x if x.starts_with("/files/") => {
+                handle_files(&request, &mut response, &config);
+            }
+            _ => {
+                response.set_status(Status::NotFound);
+            }
         }
-        _ => {
-            response.set_status(Status::NotFound);
+
+        response.headers.set_connection(keep_alive);
+
+        config.log_access(
+            &remote_addr,
+            &request.method,
+            &request.path,
+            &request.version,
+            &response.status,
+            response.body.0.len(),
+        );
+
+        reader.get_mut().write_all(&response.into_bytes())?;
+
+        if !keep_alive {
+            break;
         }
     }
 
-    stream.write_all(&response.to_bytes())?;
-
     Ok(())
 }
 
 fn handle_files(request: &Request, response: &mut Response, config: &Config) {
-    let path = config.directory.join(request.path.split_at(7).1);
+    let raw_path = percent_decode(request.path.split_at(7).1);
+    let path = config.directory.join(&raw_path);
 
     match request.method {
         Method::Get => {
-            let readable = fs::read_to_string(path);
+            let gz_path = with_appended_extension(&path, "gz");
+            let canonical_path = canonicalize_within(&config.directory, &path);
+            let canonical_gz_path = canonicalize_within(&config.directory, &gz_path);
+
+            if let Some(gz_path) = &canonical_gz_path {
+                if request.accepts_encoding("gzip") {
+                    serve_precompressed(gz_path, response);
+                    return;
+                }
+                if canonical_path.is_none() {
+                    decompress_and_serve(gz_path, response);
+                    return;
+                }
+            }
+
+            let Some(path) = canonical_path else {
+                response.set_status(Status::NotFound);
+                return;
+            };
+
+            if path.is_dir() {
+                match render_directory_listing(&path) {
+                    Ok(listing) => {
+                        response.set_html_body(Body::from_str(&listing));
+                    }
+                    Err(e) => {
+                        eprintln!("error reading directory: {}", e);
+                        response.set_status(Status::InternalServerError);
+                    }
+                }
+                return;
+            }
+
+            let readable = fs::read(&path);
 
             match readable {
                 Ok(contents) => {
-                    response.set_file_body(Body::from_str(&contents));
+                    response.headers.set_accept_ranges();
+
+                    match request
+                        .headers
+                        .get_range()
+                        .and_then(|range| parse_range(range, contents.len()))
+                    {
+                        Some(ByteRange::Satisfiable(start, end)) => {
+                            let total = contents.len();
+                            response.set_status(Status::PartialContent);
+                            response.set_file_body(&path, Body(contents[start..=end].to_vec()));
+                            response.headers.set_content_range(start, end, total);
+                        }
+                        Some(ByteRange::NotSatisfiable) => {
+                            response.set_status(Status::RangeNotSatisfiable);
+                            response
+                                .headers
+                                .set_content_range_unsatisfiable(contents.len());
+                            response.set_file_body(&path, Body::new());
+                        }
+                        None => {
+                            response.set_file_body(&path, Body(contents));
+                        }
+                    }
                 }
                 Err(e) => {
                     eprintln!("error opening file: {}", e);
@@ -106,6 +198,11 @@ fn handle_files(request: &Request, response: &mut Response, config: &Config) {
         }
 
         Method::Post => {
+            let Some(path) = resolve_for_write(&config.directory, &path) else {
+                response.set_status(Status::NotFound);
+                return;
+            };
+
             let contents = request.body.to_string();
             let result = fs::write(path, contents);
 
@@ -121,3 +218,171 @@ fn handle_files(request: &Request, response: &mut Response, config: &Config) {
         }
     }
 }
+
+/// Percent-decodes a URL path segment (e.g. `%20` -> ` `). Works on raw bytes
+/// throughout so a `%` sitting right before a multi-byte UTF-8 character
+/// can't cause a char-boundary slice panic.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = &bytes[i + 1..i + 3];
+            if hex.iter().all(u8::is_ascii_hexdigit) {
+                // Safe: both bytes were just verified to be ASCII hex digits.
+                let byte = u8::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).unwrap();
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Appends an extension to a path's existing filename, e.g. `file.txt` ->
+/// `file.txt.gz`, unlike `PathBuf::with_extension` which would replace it.
+fn with_appended_extension(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(ext);
+    PathBuf::from(name)
+}
+
+/// Streams a precompressed `.gz` file straight to the client, setting
+/// `Content-Encoding: gzip` and skipping the per-request compression pass.
+fn serve_precompressed(gz_path: &Path, response: &mut Response) {
+    match fs::read(gz_path) {
+        Ok(contents) => {
+            let original_path = gz_path.with_extension("");
+            response.set_file_body(&original_path, Body(contents));
+            response.headers.set_content_encoding(ContentEncoding::Gzip);
+        }
+        Err(e) => {
+            eprintln!("error opening file: {}", e);
+            response.set_status(Status::NotFound);
+        }
+    }
+}
+
+/// Decompresses a `.gz`-only resource in-memory for clients that didn't ask
+/// for gzip and have no plaintext sibling to fall back to.
+fn decompress_and_serve(gz_path: &Path, response: &mut Response) {
+    let Ok(contents) = fs::read(gz_path) else {
+        response.set_status(Status::NotFound);
+        return;
+    };
+
+    let mut decoder = GzDecoder::new(&contents[..]);
+    let mut decompressed = Vec::new();
+
+    match decoder.read_to_end(&mut decompressed) {
+        Ok(_) => {
+            let original_path = gz_path.with_extension("");
+            response.set_file_body(&original_path, Body(decompressed));
+        }
+        Err(e) => {
+            eprintln!("error decompressing file: {}", e);
+            response.set_status(Status::InternalServerError);
+        }
+    }
+}
+
+/// Canonicalizes `path` and checks it still lives under `root`, guarding
+/// against `..` segments escaping the configured directory.
+fn canonicalize_within(root: &Path, path: &Path) -> Option<PathBuf> {
+    let canonical_root = root.canonicalize().ok()?;
+    let canonical_path = path.canonicalize().ok()?;
+
+    canonical_path
+        .starts_with(&canonical_root)
+        .then_some(canonical_path)
+}
+
+/// Resolves a path for writing: since the target file may not exist yet,
+/// `canonicalize_within` can't be used on it directly, so this canonicalizes
+/// the (existing) parent directory instead and rejoins the file name,
+/// guarding against `..` segments escaping `root` the same way GET does.
+fn resolve_for_write(root: &Path, path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?;
+    let parent = path.parent()?;
+    let canonical_parent = canonicalize_within(root, parent)?;
+
+    Some(canonical_parent.join(file_name))
+}
+
+/// Renders a directory's entries as an HTML page of links, for browsing
+/// `/files/` when the requested path is a directory rather than a file.
+fn render_directory_listing(path: &Path) -> std::io::Result<String> {
+    let mut entries = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut html = String::from("<html><body><ul>");
+    for entry in entries {
+        let name = html_escape(&entry.file_name().to_string_lossy());
+        let href = if entry.path().is_dir() {
+            format!("{}/", name)
+        } else {
+            name.clone()
+        };
+        html.push_str(&format!("<li><a href=\"{href}\">{href}</a></li>"));
+    }
+    html.push_str("</ul></body></html>");
+
+    Ok(html)
+}
+
+/// Escapes text for safe inclusion in HTML, since directory entry names come
+/// from the filesystem (and, via the POST handler, from attacker-controlled
+/// percent-decoded request paths).
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+enum ByteRange {
+    Satisfiable(usize, usize),
+    NotSatisfiable,
+}
+
+/// Parses a `Range: bytes=start-end` header against a resource of `len` bytes,
+/// supporting closed (`0-499`), open-ended (`500-`) and suffix (`-500`) forms.
+/// Returns `None` if the header isn't a `bytes` range we understand, in which
+/// case the caller should serve the full resource instead.
+fn parse_range(header: &str, len: usize) -> Option<ByteRange> {
+    let spec = header.strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+
+    if start.is_empty() {
+        let suffix_len: usize = end.parse().ok()?;
+        if suffix_len == 0 || len == 0 {
+            return Some(ByteRange::NotSatisfiable);
+        }
+        return Some(ByteRange::Satisfiable(len.saturating_sub(suffix_len), len - 1));
+    }
+
+    let start: usize = start.parse().ok()?;
+    if start >= len {
+        return Some(ByteRange::NotSatisfiable);
+    }
+
+    let end = match end {
+        "" => len - 1,
+        end => end.parse::<usize>().ok()?.min(len - 1),
+    };
+
+    if end < start {
+        return Some(ByteRange::NotSatisfiable);
+    }
+
+    Some(ByteRange::Satisfiable(start, end))
+}
@@ -19,12 +19,12 @@ async fn main() -> Result<()> {
                 println!("accepted new connection");
 
                 let config = Arc::clone(&config);
-                tokio::spawn(async move {
-                    http_server_starter_rust::handle_connection(stream, config)
-                        .await
-                        .unwrap_or_else(|e| {
+                tokio::task::spawn_blocking(move || {
+                    http_server_starter_rust::handle_connection(stream, config).unwrap_or_else(
+                        |e| {
                             eprintln!("error: {}", e);
-                        });
+                        },
+                    );
                 });
             }
             Err(e) => {
@@ -2,10 +2,12 @@
 
 use anyhow::Result;
 use flate2::write::GzEncoder;
+use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use std::collections::HashMap;
 use std::fmt;
 use std::io::Write;
+use std::path::Path;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -35,6 +37,15 @@ impl FromStr for Method {
     }
 }
 
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Get => write!(f, "GET"),
+            Method::Post => write!(f, "POST"),
+        }
+    }
+}
+
 impl Request {
     pub fn new(
         method: Method,
@@ -52,11 +63,65 @@ impl Request {
         }
     }
 
-    pub fn is_gzip_encoding(&self) -> bool {
-        self.headers
-            .get_accept_encoding()
-            .map(|encoding| encoding.contains("gzip"))
-            .unwrap_or(false)
+    /// Parses the `Accept-Encoding` header into `(coding, q)` pairs and picks the
+    /// highest-q coding we actually support (gzip, deflate). Codings with `q=0`,
+    /// including an explicitly disallowed `identity` or `*`, are dropped.
+    pub fn preferred_encoding(&self) -> Option<ContentEncoding> {
+        let header = self.headers.get_accept_encoding()?;
+
+        let mut codings: Vec<(String, f32)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.trim().splitn(2, ";q=");
+                let coding = parts.next()?.trim().to_lowercase();
+                let q = parts
+                    .next()
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((coding, q))
+            })
+            .filter(|(_, q)| *q > 0.0)
+            .collect();
+
+        codings.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+        codings
+            .into_iter()
+            .find_map(|(coding, _)| match coding.as_str() {
+                "gzip" => Some(ContentEncoding::Gzip),
+                "deflate" => Some(ContentEncoding::Deflate),
+                _ => None,
+            })
+    }
+
+    /// Checks whether `coding` appears in `Accept-Encoding` with a non-zero q,
+    /// regardless of whether it's the client's top preference.
+    pub fn accepts_encoding(&self, coding: &str) -> bool {
+        let Some(header) = self.headers.get_accept_encoding() else {
+            return false;
+        };
+
+        header.split(',').any(|entry| {
+            let mut parts = entry.trim().splitn(2, ";q=");
+            let name = parts.next().unwrap_or("").trim().to_lowercase();
+            let q = parts
+                .next()
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            name == coding && q > 0.0
+        })
+    }
+
+    /// Decides whether the connection should stay open after this request:
+    /// honors an explicit `Connection: close`/`keep-alive`, and otherwise
+    /// defaults to closing on HTTP/1.0 and keeping alive on HTTP/1.1+.
+    pub fn wants_keep_alive(&self) -> bool {
+        match self.headers.get_connection().map(|c| c.to_lowercase()) {
+            Some(c) if c == "close" => false,
+            Some(c) if c == "keep-alive" => true,
+            _ => self.version != "HTTP/1.0",
+        }
     }
 }
 
@@ -117,11 +182,40 @@ impl Headers {
     pub fn get_user_agent(&self) -> Option<&String> {
         self.get("user-agent")
     }
+
+    pub fn get_connection(&self) -> Option<&String> {
+        self.get("connection")
+    }
+
+    pub fn set_connection(&mut self, keep_alive: bool) {
+        let value = if keep_alive { "keep-alive" } else { "close" };
+        self.set("connection".to_string(), value.to_string());
+    }
+
+    pub fn get_range(&self) -> Option<&String> {
+        self.get("range")
+    }
+
+    pub fn set_accept_ranges(&mut self) {
+        self.set("accept-ranges".to_string(), "bytes".to_string());
+    }
+
+    pub fn set_content_range(&mut self, start: usize, end: usize, total: usize) {
+        self.set(
+            "content-range".to_string(),
+            format!("bytes {}-{}/{}", start, end, total),
+        );
+    }
+
+    pub fn set_content_range_unsatisfiable(&mut self, total: usize) {
+        self.set("content-range".to_string(), format!("bytes */{}", total));
+    }
 }
 
 pub enum ContentType {
     PlainText,
     OctetStream,
+    Other(String),
 }
 
 impl fmt::Display for ContentType {
@@ -129,18 +223,39 @@ impl fmt::Display for ContentType {
         match self {
             ContentType::PlainText => write!(f, "text/plain"),
             ContentType::OctetStream => write!(f, "application/octet-stream"),
+            ContentType::Other(mime) => write!(f, "{}", mime),
+        }
+    }
+}
+
+impl ContentType {
+    /// Guesses a media type from a file's extension, falling back to
+    /// `application/octet-stream` for anything we don't recognize.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("html") => ContentType::Other("text/html".to_string()),
+            Some("css") => ContentType::Other("text/css".to_string()),
+            Some("js") => ContentType::Other("text/javascript".to_string()),
+            Some("json") => ContentType::Other("application/json".to_string()),
+            Some("png") => ContentType::Other("image/png".to_string()),
+            Some("jpg") | Some("jpeg") => ContentType::Other("image/jpeg".to_string()),
+            Some("gif") => ContentType::Other("image/gif".to_string()),
+            Some("txt") => ContentType::PlainText,
+            _ => ContentType::OctetStream,
         }
     }
 }
 
 pub enum ContentEncoding {
     Gzip,
+    Deflate,
 }
 
 impl fmt::Display for ContentEncoding {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ContentEncoding::Gzip => write!(f, "gzip"),
+            ContentEncoding::Deflate => write!(f, "deflate"),
         }
     }
 }
@@ -189,8 +304,10 @@ pub struct Response {
 #[derive(Debug)]
 pub enum Status {
     Ok,
+    PartialContent,
     NotFound,
     Created,
+    RangeNotSatisfiable,
     InternalServerError,
 }
 
@@ -198,8 +315,10 @@ impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let status = match self {
             Status::Ok => "200 OK",
+            Status::PartialContent => "206 Partial Content",
             Status::NotFound => "404 Not Found",
             Status::Created => "201 Created",
+            Status::RangeNotSatisfiable => "416 Range Not Satisfiable",
             Status::InternalServerError => "500 Internal Server Error",
         };
 
@@ -207,6 +326,19 @@ impl fmt::Display for Status {
     }
 }
 
+impl Status {
+    pub fn code(&self) -> u16 {
+        match self {
+            Status::Ok => 200,
+            Status::PartialContent => 206,
+            Status::Created => 201,
+            Status::NotFound => 404,
+            Status::RangeNotSatisfiable => 416,
+            Status::InternalServerError => 500,
+        }
+    }
+}
+
 impl Response {
     pub fn new() -> Self {
         Self {
@@ -231,28 +363,52 @@ impl Response {
         self.body = body;
     }
 
-    pub fn set_file_body(&mut self, body: Body) {
-        self.headers.set_content_type(ContentType::OctetStream);
+    pub fn set_html_body(&mut self, body: Body) {
+        self.headers
+            .set_content_type(ContentType::Other("text/html".to_string()));
+        self.headers.set_content_length(body.0.len());
+        self.body = body;
+    }
+
+    pub fn set_file_body(&mut self, path: &Path, body: Body) {
+        self.headers.set_content_type(ContentType::from_path(path));
         self.headers.set_content_length(body.0.len());
         self.body = body;
     }
 
     pub fn apply_compression(&mut self, request: &Request) {
-        if !request.is_gzip_encoding() {
+        if self.body.0.is_empty() {
             return;
         }
 
-        self.headers.set_content_encoding(ContentEncoding::Gzip);
+        let Some(encoding) = request.preferred_encoding() else {
+            return;
+        };
 
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(&self.body.0).unwrap();
-        let compressed_body = encoder.finish().unwrap();
+        let compressed_body = match encoding {
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&self.body.0).unwrap();
+                encoder.finish().unwrap()
+            }
+            ContentEncoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&self.body.0).unwrap();
+                encoder.finish().unwrap()
+            }
+        };
 
+        self.headers.set_content_encoding(encoding);
         self.body = Body(compressed_body);
         self.headers.set_content_length(self.body.0.len());
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        // Every response must carry framing info or a keep-alive connection
+        // can't tell where it ends, so set this unconditionally rather than
+        // relying on each route to have called a `set_*_body` helper.
+        self.headers.set_content_length(self.body.0.len());
+
         let mut buffer = Vec::new();
 
         buffer.extend_from_slice(format!("{} ", self.version).as_bytes());